@@ -19,6 +19,7 @@ use std::sync::Arc;
 use std::time::Duration;
 
 use async_trait::async_trait;
+use futures::future::join_all;
 use log::{debug, warn};
 
 use crate::scheduler_server::event::SchedulerServerEvent;
@@ -27,29 +28,150 @@ use crate::state::task_scheduler::TaskScheduler;
 use crate::state::SchedulerState;
 use ballista_core::error::{BallistaError, Result};
 use ballista_core::event_loop::EventAction;
-use ballista_core::serde::protobuf::{LaunchTaskParams, TaskDefinition};
-use ballista_core::serde::scheduler::ExecutorDataChange;
+use ballista_core::serde::protobuf::{LaunchTaskParams, PartitionId, TaskDefinition};
+use ballista_core::serde::scheduler::{ExecutorData, ExecutorDataChange};
 use ballista_core::serde::AsExecutionPlan;
 use datafusion_proto::logical_plan::AsLogicalPlan;
 
+/// Safety net for the notification-based wait in `offer_resources`: if no
+/// executor reports free capacity within this window, retry anyway rather
+/// than waiting forever on a notification that might never come. Kept short
+/// (rather than the original poll interval's generous multiple) because this
+/// wait runs inline on the single-consumer event loop that also has to react
+/// promptly to events like `DecommissionExecutor`.
+const EXECUTOR_CAPACITY_WAIT_TIMEOUT: Duration = Duration::from_millis(250);
+
+/// Controls how schedulable tasks are handed out to executors in
+/// [`SchedulerServerEventAction::offer_resources`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TaskAssignmentPolicy {
+    /// Walk the available executors and hand each one a round-robin slice
+    /// of schedulable work. This is the original behavior: simple, but it
+    /// can spread a job's tasks thinly and doesn't prioritize which tasks
+    /// are actually ready to run.
+    RoundRobin,
+    /// Build a single ordered queue of all currently schedulable tasks
+    /// across jobs (by job submission time, then stage id, then partition
+    /// id) and drain it against whichever executor still has a free slot.
+    /// A single busy job can't starve others, since assignment is driven
+    /// by the task queue rather than by looping executors.
+    TaskFirst,
+}
+
+/// Decides how the [`TaskAssignmentPolicy::TaskFirst`] queue is packed into
+/// free executor slots, so operators can tune between even spread and tight
+/// packing without touching the event loop itself.
+pub trait SlotReservationPolicy: Send + Sync {
+    /// Packs `tasks` into the free slots of `executors`, claiming at most
+    /// `wanted` tasks in total and decrementing each executor's
+    /// `available_task_slots` as a slot is reserved. Returns the resulting
+    /// per-executor assignment, indexed the same as `executors`.
+    fn reserve(
+        &self,
+        executors: &mut [ExecutorData],
+        tasks: Vec<TaskDefinition>,
+        wanted: u32,
+    ) -> Vec<Vec<TaskDefinition>>;
+}
+
+/// Hands tasks to executors in round-robin order, so a job's work is spread
+/// evenly rather than piling onto one executor.
+pub struct RoundRobinSlotReservation;
+
+impl SlotReservationPolicy for RoundRobinSlotReservation {
+    fn reserve(
+        &self,
+        executors: &mut [ExecutorData],
+        tasks: Vec<TaskDefinition>,
+        wanted: u32,
+    ) -> Vec<Vec<TaskDefinition>> {
+        let mut tasks_assigment: Vec<Vec<TaskDefinition>> =
+            vec![Vec::new(); executors.len()];
+        let mut remaining = wanted as usize;
+        let mut idx = 0;
+
+        'tasks: for task in tasks {
+            if remaining == 0 || executors.is_empty() {
+                break;
+            }
+            let start = idx;
+            loop {
+                if executors[idx].available_task_slots > 0 {
+                    executors[idx].available_task_slots -= 1;
+                    tasks_assigment[idx].push(task);
+                    remaining -= 1;
+                    idx = (idx + 1) % executors.len();
+                    continue 'tasks;
+                }
+                idx = (idx + 1) % executors.len();
+                if idx == start {
+                    // No executor has a free slot left.
+                    break 'tasks;
+                }
+            }
+        }
+
+        tasks_assigment
+    }
+}
+
+/// Fills one executor to capacity before moving on to the next,
+/// consolidating work instead of spreading it. Useful when scaling
+/// executors down or when co-locating a stage's tasks improves data
+/// locality.
+pub struct BinPackingSlotReservation;
+
+impl SlotReservationPolicy for BinPackingSlotReservation {
+    fn reserve(
+        &self,
+        executors: &mut [ExecutorData],
+        tasks: Vec<TaskDefinition>,
+        wanted: u32,
+    ) -> Vec<Vec<TaskDefinition>> {
+        let mut tasks_assigment: Vec<Vec<TaskDefinition>> =
+            vec![Vec::new(); executors.len()];
+        let mut remaining = wanted as usize;
+        let mut tasks = tasks.into_iter();
+
+        for (idx, executor_data) in executors.iter_mut().enumerate() {
+            while remaining > 0 && executor_data.available_task_slots > 0 {
+                let task = match tasks.next() {
+                    Some(task) => task,
+                    None => return tasks_assigment,
+                };
+                executor_data.available_task_slots -= 1;
+                tasks_assigment[idx].push(task);
+                remaining -= 1;
+            }
+        }
+
+        tasks_assigment
+    }
+}
+
 pub(crate) struct SchedulerServerEventAction<
     T: 'static + AsLogicalPlan,
     U: 'static + AsExecutionPlan,
 > {
     state: Arc<SchedulerState<T, U>>,
     executors_client: ExecutorsClient,
+    task_assignment_policy: TaskAssignmentPolicy,
+    slot_reservation_policy: Arc<dyn SlotReservationPolicy>,
 }
 
 impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan>
     SchedulerServerEventAction<T, U>
 {
-    pub fn new(
-        state: Arc<SchedulerState<T, U>>,
-        executors_client: ExecutorsClient,
-    ) -> Self {
+    pub fn new(state: Arc<SchedulerState<T, U>>, executors_client: ExecutorsClient) -> Self {
+        // Both policies come from the state's `SchedulerConfig` rather
+        // than being picked by callers of this constructor.
+        let task_assignment_policy = state.config().task_assignment_policy();
+        let slot_reservation_policy = state.config().slot_reservation_policy();
         Self {
             state,
             executors_client,
+            task_assignment_policy,
+            slot_reservation_policy,
         }
     }
 
@@ -57,11 +179,21 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan>
     async fn offer_resources(&self, n: u32) -> Result<Option<SchedulerServerEvent>> {
         let mut available_executors =
             self.state.executor_manager.get_available_executors_data();
-        // In case of there's no enough resources, reschedule the tasks of the job
+        // In case of there's no enough resources, wait for one to free up
+        // instead of polling on a blind sleep. `Notify` holds at most one
+        // permit, so a burst of executors reporting completions or new
+        // registrations while nobody's listening still wakes us exactly
+        // once, which naturally coalesces the resulting revive into a
+        // single scheduling pass.
         if available_executors.is_empty() {
-            // TODO Maybe it's better to use an exclusive runtime for this kind task scheduling
-            warn!("Not enough available executors for task running");
-            tokio::time::sleep(Duration::from_millis(100)).await;
+            warn!("Not enough available executors for task running, waiting for capacity");
+            let notify = self.state.executor_manager.available_slots_notify();
+            tokio::select! {
+                _ = notify.notified() => {}
+                _ = tokio::time::sleep(EXECUTOR_CAPACITY_WAIT_TIMEOUT) => {
+                    debug!("Timed out waiting for executor capacity, retrying anyway");
+                }
+            }
             return Ok(Some(SchedulerServerEvent::ReviveOffers(1)));
         }
 
@@ -73,10 +205,17 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan>
             })
             .collect();
 
-        let (tasks_assigment, num_tasks) = self
-            .state
-            .fetch_schedulable_tasks(&mut available_executors, n)
-            .await?;
+        let (tasks_assigment, num_tasks) = match self.task_assignment_policy {
+            TaskAssignmentPolicy::RoundRobin => {
+                self.state
+                    .fetch_schedulable_tasks(&mut available_executors, n)
+                    .await?
+            }
+            TaskAssignmentPolicy::TaskFirst => {
+                self.fetch_schedulable_tasks_task_first(&mut available_executors, n)
+                    .await?
+            }
+        };
         for (data_change, data) in executors_data_change
             .iter_mut()
             .zip(available_executors.iter())
@@ -86,62 +225,175 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan>
         }
 
         #[cfg(not(test))]
-        if num_tasks > 0 {
-            self.launch_tasks(&executors_data_change, tasks_assigment)
-                .await?;
+        if num_tasks > 0
+            && self
+                .launch_tasks(&executors_data_change, tasks_assigment)
+                .await?
+        {
+            // Some tasks failed to launch and were pushed back onto the
+            // schedulable queue; revive offers so they get re-assigned
+            // instead of being silently stranded.
+            return Ok(Some(SchedulerServerEvent::ReviveOffers(1)));
         }
 
         Ok(None)
     }
 
+    /// Task-first counterpart to [`TaskScheduler::fetch_schedulable_tasks`].
+    /// Pulls a single ordered queue of schedulable tasks across all jobs and
+    /// drains it against whichever executor still has a free slot, instead
+    /// of looping over executors and handing each a round-robin slice.
+    async fn fetch_schedulable_tasks_task_first(
+        &self,
+        available_executors: &mut [ExecutorData],
+        n: u32,
+    ) -> Result<(Vec<Vec<TaskDefinition>>, usize)> {
+        let schedulable_tasks = self
+            .state
+            .get_schedulable_tasks_queue(available_executors, n)
+            .await?;
+
+        let tasks_assigment = self.slot_reservation_policy.reserve(
+            available_executors,
+            schedulable_tasks,
+            n,
+        );
+        let num_tasks = tasks_assigment.iter().map(Vec::len).sum();
+
+        Ok((tasks_assigment, num_tasks))
+    }
+
+    /// Launches `tasks_assigment` on their assigned executors, one
+    /// `launch_task` RPC per executor (already batching that executor's
+    /// whole slice of tasks into a single message) dispatched concurrently.
+    ///
+    /// Returns `Ok(true)` if one or more executors failed to accept their
+    /// tasks, meaning the tasks were pushed back onto the schedulable queue
+    /// and the caller should revive offers so they get re-assigned.
     #[allow(dead_code)]
     async fn launch_tasks(
         &self,
         executors: &[ExecutorDataChange],
         tasks_assigment: Vec<Vec<TaskDefinition>>,
-    ) -> Result<()> {
-        for (idx_executor, tasks) in tasks_assigment.into_iter().enumerate() {
-            if !tasks.is_empty() {
-                let executor_data_change = &executors[idx_executor];
-                debug!(
-                    "Start to launch tasks {:?} to executor {:?}",
-                    tasks
-                        .iter()
-                        .map(|task| {
-                            if let Some(task_id) = task.task_id.as_ref() {
-                                format!(
-                                    "{}/{}/{}",
-                                    task_id.job_id,
-                                    task_id.stage_id,
-                                    task_id.partition_id
-                                )
-                            } else {
-                                "".to_string()
-                            }
-                        })
-                        .collect::<Vec<String>>(),
-                    executor_data_change.executor_id
-                );
-                let mut client = {
-                    let clients = self.executors_client.read().await;
-                    clients
-                        .get(&executor_data_change.executor_id)
-                        .unwrap()
-                        .clone()
-                };
-                // TODO check whether launching task is successful or not
-                client.launch_task(LaunchTaskParams { task: tasks }).await?;
+    ) -> Result<bool> {
+        let launches = tasks_assigment
+            .into_iter()
+            .enumerate()
+            .filter(|(_, tasks)| !tasks.is_empty())
+            .map(|(idx_executor, tasks)| {
+                let executor_data_change = executors[idx_executor].clone();
+                async move {
+                    debug!(
+                        "Start to launch tasks {:?} to executor {:?}",
+                        tasks
+                            .iter()
+                            .map(|task| {
+                                if let Some(task_id) = task.task_id.as_ref() {
+                                    format!(
+                                        "{}/{}/{}",
+                                        task_id.job_id,
+                                        task_id.stage_id,
+                                        task_id.partition_id
+                                    )
+                                } else {
+                                    "".to_string()
+                                }
+                            })
+                            .collect::<Vec<String>>(),
+                        executor_data_change.executor_id
+                    );
+                    // Keep the (cheap) task ids around for a possible
+                    // reschedule, rather than cloning the full task
+                    // definitions (which can carry serialized execution
+                    // plans) on every launch just to cover the rare
+                    // failure path.
+                    let task_ids: Vec<PartitionId> =
+                        tasks.iter().filter_map(|t| t.task_id.clone()).collect();
+                    let mut client = {
+                        let clients = self.executors_client.read().await;
+                        clients
+                            .get(&executor_data_change.executor_id)
+                            .unwrap()
+                            .clone()
+                    };
+                    let result = client
+                        .launch_task(LaunchTaskParams { task: tasks })
+                        .await
+                        .map(|_| ());
+                    (executor_data_change, task_ids, result)
+                }
+            });
+
+        let mut needs_revive = false;
+        for (executor_data_change, task_ids, result) in join_all(launches).await {
+            if self.record_launch_result(executor_data_change, task_ids, result) {
+                needs_revive = true;
+            }
+        }
+
+        Ok(needs_revive)
+    }
+
+    /// Applies the bookkeeping a single executor's launch result implies:
+    /// on success, applies its slot-count change (the slots the launch
+    /// consumed); on failure, evicts the executor
+    /// (unless the error looks transient) and reschedules its tasks.
+    /// Returns whether offers should be revived. Kept separate from the
+    /// network dispatch in `launch_tasks` so the bookkeeping can be
+    /// unit-tested without a live executor connection.
+    fn record_launch_result(
+        &self,
+        executor_data_change: ExecutorDataChange,
+        task_ids: Vec<PartitionId>,
+        result: Result<()>,
+    ) -> bool {
+        match result {
+            Ok(()) => {
                 self.state
                     .executor_manager
-                    .update_executor_data(executor_data_change);
-            } else {
-                // Since the task assignment policy is round robin,
-                // if find tasks for one executor is empty, just break fast
-                break;
+                    .update_executor_data(&executor_data_change);
+                false
+            }
+            Err(e) => {
+                // Don't decrement this executor's slots: it never actually
+                // took the tasks. A connection-level failure is worth
+                // retrying on the same executor later; anything else (the
+                // executor itself rejecting the launch) means it should be
+                // evicted so it can't keep stranding work.
+                if is_transient_launch_error(&e) {
+                    warn!(
+                        "Transient error launching tasks on executor {}, will retry: {:?}",
+                        executor_data_change.executor_id, e
+                    );
+                } else {
+                    warn!(
+                        "Executor {} failed to launch tasks, marking it unreachable: {:?}",
+                        executor_data_change.executor_id, e
+                    );
+                    self.state
+                        .executor_manager
+                        .remove_executor(&executor_data_change.executor_id);
+                }
+                self.state.reschedule_tasks(task_ids);
+                true
             }
         }
+    }
 
-        Ok(())
+    /// Stops assigning new work to `executor_id` right away, removing it
+    /// from the pool used by `get_available_executors_data`. Tasks already
+    /// running on it are left alone so in-flight work isn't killed
+    /// mid-flight; whatever was still queued for it gets re-offered to the
+    /// rest of the pool.
+    async fn decommission_executor(
+        &self,
+        executor_id: String,
+    ) -> Result<Option<SchedulerServerEvent>> {
+        debug!("Decommissioning executor {}", executor_id);
+        self.state
+            .executor_manager
+            .decommission_executor(&executor_id);
+        Ok(Some(SchedulerServerEvent::ReviveOffers(1)))
     }
 }
 
@@ -161,9 +413,238 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan>
     ) -> Result<Option<SchedulerServerEvent>> {
         match event {
             SchedulerServerEvent::ReviveOffers(n) => self.offer_resources(n).await,
+            SchedulerServerEvent::DecommissionExecutor(executor_id) => {
+                self.decommission_executor(executor_id).await
+            }
         }
     }
 
     // TODO
     fn on_error(&self, _error: BallistaError) {}
 }
+
+/// Connection-level failures (the executor is momentarily unreachable, the
+/// channel reset, etc.) are worth retrying on the same executor; anything
+/// else indicates the executor itself is misbehaving and should be evicted.
+/// `BallistaError` doesn't carry a dedicated variant for "this was just a
+/// transport hiccup", so walk the error's `source()` chain for the
+/// underlying `tonic::Status`/`tonic::transport::Error` and match on its
+/// actual code rather than on `Display` text, which is brittle against
+/// wording changes.
+fn is_transient_launch_error(err: &BallistaError) -> bool {
+    is_transient_grpc_error(err)
+}
+
+fn is_transient_grpc_error(err: &(dyn std::error::Error + 'static)) -> bool {
+    if let Some(status) = err.downcast_ref::<tonic::Status>() {
+        return matches!(
+            status.code(),
+            tonic::Code::Unavailable | tonic::Code::DeadlineExceeded | tonic::Code::Aborted
+        );
+    }
+    if err.downcast_ref::<tonic::transport::Error>().is_some() {
+        return true;
+    }
+    err.source().map_or(false, is_transient_grpc_error)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ballista_core::serde::protobuf::{LogicalPlanNode, PhysicalPlanNode};
+
+    fn executor(executor_id: &str, available_task_slots: u32) -> ExecutorData {
+        ExecutorData {
+            executor_id: executor_id.to_string(),
+            total_task_slots: available_task_slots,
+            available_task_slots,
+        }
+    }
+
+    fn task(partition_id: u32) -> TaskDefinition {
+        TaskDefinition {
+            task_id: Some(PartitionId {
+                job_id: "job".to_string(),
+                stage_id: 0,
+                partition_id,
+            }),
+            ..Default::default()
+        }
+    }
+
+    /// No matter how the tasks are packed, no executor should ever end up
+    /// with more tasks assigned than it had free slots to begin with.
+    fn assert_respects_free_slots(
+        free_slots_before: &[u32],
+        tasks_assigment: &[Vec<TaskDefinition>],
+    ) {
+        for (slots, assigned) in free_slots_before.iter().zip(tasks_assigment.iter()) {
+            assert!(
+                (assigned.len() as u32) <= *slots,
+                "executor assigned {} tasks but only had {} free slots",
+                assigned.len(),
+                slots
+            );
+        }
+    }
+
+    #[test]
+    fn round_robin_reservation_respects_free_slots() {
+        let mut executors = vec![executor("a", 1), executor("b", 2), executor("c", 0)];
+        let free_slots_before: Vec<u32> =
+            executors.iter().map(|e| e.available_task_slots).collect();
+        let tasks: Vec<TaskDefinition> = (0..10).map(task).collect();
+
+        let tasks_assigment =
+            RoundRobinSlotReservation.reserve(&mut executors, tasks, 10);
+
+        assert_respects_free_slots(&free_slots_before, &tasks_assigment);
+        let total_assigned: usize = tasks_assigment.iter().map(Vec::len).sum();
+        assert_eq!(total_assigned, 3);
+    }
+
+    #[test]
+    fn round_robin_reservation_spreads_tasks_before_filling_one_executor() {
+        let mut executors = vec![executor("a", 2), executor("b", 2)];
+        let tasks: Vec<TaskDefinition> = (0..2).map(task).collect();
+
+        let tasks_assigment =
+            RoundRobinSlotReservation.reserve(&mut executors, tasks, 2);
+
+        assert_eq!(tasks_assigment[0].len(), 1);
+        assert_eq!(tasks_assigment[1].len(), 1);
+    }
+
+    #[test]
+    fn bin_packing_reservation_respects_free_slots() {
+        let mut executors = vec![executor("a", 1), executor("b", 2), executor("c", 0)];
+        let free_slots_before: Vec<u32> =
+            executors.iter().map(|e| e.available_task_slots).collect();
+        let tasks: Vec<TaskDefinition> = (0..10).map(task).collect();
+
+        let tasks_assigment =
+            BinPackingSlotReservation.reserve(&mut executors, tasks, 10);
+
+        assert_respects_free_slots(&free_slots_before, &tasks_assigment);
+        let total_assigned: usize = tasks_assigment.iter().map(Vec::len).sum();
+        assert_eq!(total_assigned, 3);
+    }
+
+    #[test]
+    fn bin_packing_reservation_fills_one_executor_before_the_next() {
+        let mut executors = vec![executor("a", 2), executor("b", 2)];
+        let tasks: Vec<TaskDefinition> = (0..2).map(task).collect();
+
+        let tasks_assigment =
+            BinPackingSlotReservation.reserve(&mut executors, tasks, 2);
+
+        assert_eq!(tasks_assigment[0].len(), 2);
+        assert_eq!(tasks_assigment[1].len(), 0);
+    }
+
+    #[test]
+    fn reservation_stops_at_wanted_even_with_free_slots_remaining() {
+        let mut executors = vec![executor("a", 5)];
+        let tasks: Vec<TaskDefinition> = (0..5).map(task).collect();
+
+        let tasks_assigment = RoundRobinSlotReservation.reserve(&mut executors, tasks, 2);
+
+        let total_assigned: usize = tasks_assigment.iter().map(Vec::len).sum();
+        assert_eq!(total_assigned, 2);
+    }
+
+    fn action() -> SchedulerServerEventAction<LogicalPlanNode, PhysicalPlanNode> {
+        SchedulerServerEventAction::new(
+            Arc::new(SchedulerState::default()),
+            ExecutorsClient::default(),
+        )
+    }
+
+    #[test]
+    fn record_launch_result_updates_slots_on_success_and_does_not_revive() {
+        let action = action();
+        action
+            .state
+            .executor_manager
+            .register_executor(executor("a", 4));
+        // As computed by `offer_resources`: 2 of the 4 slots were consumed.
+        let change = ExecutorDataChange {
+            executor_id: "a".to_string(),
+            task_slots: -2,
+        };
+
+        let needs_revive = action.record_launch_result(change, vec![], Ok(()));
+
+        assert!(!needs_revive);
+        let remaining = action
+            .state
+            .executor_manager
+            .get_available_executors_data()
+            .into_iter()
+            .find(|e| e.executor_id == "a")
+            .unwrap()
+            .available_task_slots;
+        assert_eq!(remaining, 2);
+    }
+
+    #[test]
+    fn record_launch_result_reschedules_tasks_and_requests_revive_on_failure() {
+        let action = action();
+        action
+            .state
+            .executor_manager
+            .register_executor(executor("a", 4));
+        action.state.push_schedulable_task(task(0));
+        let popped_id = action
+            .state
+            .pop_schedulable_task()
+            .unwrap()
+            .task_id
+            .unwrap();
+        let change = ExecutorDataChange {
+            executor_id: "a".to_string(),
+            task_slots: -1,
+        };
+
+        let needs_revive = action.record_launch_result(
+            change,
+            vec![popped_id.clone()],
+            Err(BallistaError::General("executor rejected launch".to_string())),
+        );
+
+        assert!(needs_revive);
+        // A non-transient failure evicts the executor...
+        assert!(action
+            .state
+            .executor_manager
+            .get_available_executors_data()
+            .is_empty());
+        // ...and the task goes back to the front of the schedulable queue.
+        assert_eq!(
+            action
+                .state
+                .pop_schedulable_task()
+                .unwrap()
+                .task_id
+                .unwrap(),
+            popped_id
+        );
+    }
+
+    #[tokio::test]
+    async fn decommission_executor_stops_offering_new_work() {
+        let action = action();
+        action
+            .state
+            .executor_manager
+            .register_executor(executor("a", 2));
+
+        action.decommission_executor("a".to_string()).await.unwrap();
+
+        assert!(action
+            .state
+            .executor_manager
+            .get_available_executors_data()
+            .is_empty());
+    }
+}
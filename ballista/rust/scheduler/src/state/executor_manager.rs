@@ -0,0 +1,211 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+use std::collections::HashMap;
+use std::sync::RwLock;
+
+use tokio::sync::Notify;
+
+use ballista_core::serde::scheduler::{ExecutorData, ExecutorDataChange};
+
+/// Tracks which executors are currently known to the scheduler and how much
+/// free task-slot capacity each one has.
+#[derive(Default)]
+pub(crate) struct ExecutorManager {
+    executors_data: RwLock<HashMap<String, ExecutorData>>,
+    /// Notified whenever an executor's free task-slot count might have gone
+    /// up, so `offer_resources` can wait on capacity instead of polling.
+    slots_notify: Notify,
+}
+
+impl ExecutorManager {
+    /// Registers `data` as a known executor (or replaces its entry if
+    /// already registered), making it eligible for scheduling.
+    pub fn register_executor(&self, data: ExecutorData) {
+        let has_free_slots = data.available_task_slots > 0;
+        self.executors_data
+            .write()
+            .unwrap()
+            .insert(data.executor_id.clone(), data);
+        if has_free_slots {
+            self.slots_notify.notify_one();
+        }
+    }
+
+    /// Executors that currently have at least one free task slot.
+    pub fn get_available_executors_data(&self) -> Vec<ExecutorData> {
+        self.executors_data
+            .read()
+            .unwrap()
+            .values()
+            .filter(|data| data.available_task_slots > 0)
+            .cloned()
+            .collect()
+    }
+
+    /// Applies a slot-count delta reported after scheduling a batch of
+    /// tasks (negative: slots just consumed) or after a task finishes
+    /// (positive: a slot freed back up). The result is kept within
+    /// `0..=total_task_slots`.
+    pub fn update_executor_data(&self, change: &ExecutorDataChange) {
+        let mut became_available = false;
+        if let Some(data) = self
+            .executors_data
+            .write()
+            .unwrap()
+            .get_mut(&change.executor_id)
+        {
+            data.available_task_slots = (data.available_task_slots as i32
+                + change.task_slots)
+                .clamp(0, data.total_task_slots as i32)
+                as u32;
+            became_available = data.available_task_slots > 0;
+        }
+        if became_available {
+            self.slots_notify.notify_one();
+        }
+    }
+
+    /// Returns the `Notify` that fires when an executor's free task-slot
+    /// count might have gone up, so callers can wait on new capacity
+    /// instead of polling on a sleep.
+    pub fn available_slots_notify(&self) -> &Notify {
+        &self.slots_notify
+    }
+
+    /// Evicts `executor_id` entirely. Meant for an executor presumed dead
+    /// (e.g. a failed launch that wasn't a transient connection error):
+    /// there's nothing left to preserve for it, so any bookkeeping keyed by
+    /// this id stops being serviced too.
+    pub fn remove_executor(&self, executor_id: &str) {
+        self.executors_data.write().unwrap().remove(executor_id);
+    }
+
+    /// Stops handing `executor_id` new work, without removing it. Its entry
+    /// is left in place (unlike `remove_executor`'s hard eviction) so tasks
+    /// already running on it can still be tracked through to completion
+    /// while it drains.
+    pub fn decommission_executor(&self, executor_id: &str) {
+        if let Some(data) = self.executors_data.write().unwrap().get_mut(executor_id) {
+            // Zeroing the total too keeps later freed-slot deltas from
+            // making it schedulable again.
+            data.total_task_slots = 0;
+            data.available_task_slots = 0;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use super::*;
+
+    fn executor(executor_id: &str, slots: u32) -> ExecutorData {
+        ExecutorData {
+            executor_id: executor_id.to_string(),
+            total_task_slots: slots,
+            available_task_slots: slots,
+        }
+    }
+
+    fn change(executor_id: &str, task_slots: i32) -> ExecutorDataChange {
+        ExecutorDataChange {
+            executor_id: executor_id.to_string(),
+            task_slots,
+        }
+    }
+
+    #[test]
+    fn update_executor_data_applies_a_delta_not_a_replacement() {
+        let manager = ExecutorManager::default();
+        manager.register_executor(executor("a", 4));
+
+        // Consume 1 of 4 slots, as `offer_resources` does after assigning
+        // fewer tasks than an executor had free.
+        manager.update_executor_data(&change("a", -1));
+
+        let remaining = manager
+            .get_available_executors_data()
+            .into_iter()
+            .find(|e| e.executor_id == "a")
+            .unwrap()
+            .available_task_slots;
+        assert_eq!(remaining, 3);
+    }
+
+    #[test]
+    fn update_executor_data_does_not_go_below_zero() {
+        let manager = ExecutorManager::default();
+        manager.register_executor(executor("a", 1));
+
+        manager.update_executor_data(&change("a", -5));
+
+        assert!(manager
+            .get_available_executors_data()
+            .iter()
+            .all(|e| e.executor_id != "a"));
+    }
+
+    #[test]
+    fn remove_executor_drops_it_from_the_pool() {
+        let manager = ExecutorManager::default();
+        manager.register_executor(executor("a", 2));
+
+        manager.remove_executor("a");
+
+        assert!(manager.get_available_executors_data().is_empty());
+        assert!(!manager.executors_data.read().unwrap().contains_key("a"));
+    }
+
+    #[test]
+    fn decommission_executor_excludes_it_without_removing_it() {
+        let manager = ExecutorManager::default();
+        manager.register_executor(executor("a", 2));
+
+        manager.decommission_executor("a");
+
+        assert!(manager.get_available_executors_data().is_empty());
+        assert!(manager.executors_data.read().unwrap().contains_key("a"));
+    }
+
+    #[tokio::test]
+    async fn available_slots_notify_wakes_on_freed_capacity() {
+        let manager = Arc::new(ExecutorManager::default());
+        manager.register_executor(executor("a", 1));
+        // Take the wake-up stored by the registration itself.
+        manager.available_slots_notify().notified().await;
+        // Consume the only slot so a caller would have to wait for capacity.
+        manager.update_executor_data(&change("a", -1));
+
+        let waiter = {
+            let manager = manager.clone();
+            tokio::spawn(async move {
+                manager.available_slots_notify().notified().await;
+            })
+        };
+        tokio::task::yield_now().await;
+
+        manager.update_executor_data(&change("a", 1));
+
+        tokio::time::timeout(Duration::from_secs(1), waiter)
+            .await
+            .expect("notify should wake the waiter promptly")
+            .unwrap();
+    }
+}
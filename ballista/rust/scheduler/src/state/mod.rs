@@ -0,0 +1,248 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+pub(crate) mod executor_manager;
+pub(crate) mod task_scheduler;
+
+use std::collections::{HashMap, VecDeque};
+use std::marker::PhantomData;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
+
+use ballista_core::serde::protobuf::{PartitionId, TaskDefinition};
+use ballista_core::serde::AsExecutionPlan;
+use datafusion_proto::logical_plan::AsLogicalPlan;
+
+use crate::scheduler_server::event_loop::{
+    RoundRobinSlotReservation, SlotReservationPolicy, TaskAssignmentPolicy,
+};
+use executor_manager::ExecutorManager;
+
+/// Scheduling policies read by the event loop. Only `Default` builds one
+/// (round-robin task assignment and round-robin slot reservation); there is
+/// no constructor or setter for other values, and `SchedulerState` is only
+/// built through `Default`, so it always runs with these defaults.
+pub(crate) struct SchedulerConfig {
+    task_assignment_policy: TaskAssignmentPolicy,
+    slot_reservation_policy: Arc<dyn SlotReservationPolicy>,
+}
+
+impl SchedulerConfig {
+    pub fn task_assignment_policy(&self) -> TaskAssignmentPolicy {
+        self.task_assignment_policy
+    }
+
+    pub fn slot_reservation_policy(&self) -> Arc<dyn SlotReservationPolicy> {
+        self.slot_reservation_policy.clone()
+    }
+}
+
+impl Default for SchedulerConfig {
+    fn default() -> Self {
+        Self {
+            task_assignment_policy: TaskAssignmentPolicy::RoundRobin,
+            slot_reservation_policy: Arc::new(RoundRobinSlotReservation),
+        }
+    }
+}
+
+pub(crate) struct SchedulerState<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> {
+    pub executor_manager: ExecutorManager,
+    config: SchedulerConfig,
+    /// Kept ordered by job submission order, then stage id, then partition
+    /// id (see `push_schedulable_task`), so `pop_schedulable_task` can stay
+    /// a plain `pop_front`.
+    pending_tasks: RwLock<VecDeque<TaskDefinition>>,
+    /// Assigns each job a submission sequence number the first time one of
+    /// its tasks is pushed, so task order can be compared across jobs
+    /// without needing a wall-clock submission timestamp.
+    job_sequence: RwLock<HashMap<String, u64>>,
+    next_job_sequence: AtomicU64,
+    /// Tasks already popped by `pop_schedulable_task` and handed out to an
+    /// executor, but not yet confirmed launched, keyed by
+    /// `job_id/stage_id/partition_id`. Lets a failed launch find its way
+    /// back onto `pending_tasks` from nothing more than the `PartitionId`
+    /// the caller kept around, instead of requiring it to hold onto the
+    /// full (possibly plan-carrying) `TaskDefinition`.
+    in_flight: RwLock<HashMap<String, TaskDefinition>>,
+    _marker: PhantomData<(T, U)>,
+}
+
+impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> Default for SchedulerState<T, U> {
+    fn default() -> Self {
+        Self {
+            executor_manager: ExecutorManager::default(),
+            config: SchedulerConfig::default(),
+            pending_tasks: RwLock::new(VecDeque::new()),
+            job_sequence: RwLock::new(HashMap::new()),
+            next_job_sequence: AtomicU64::new(0),
+            in_flight: RwLock::new(HashMap::new()),
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> SchedulerState<T, U> {
+    pub fn config(&self) -> &SchedulerConfig {
+        &self.config
+    }
+
+    /// Inserts `task` into the schedulable queue at the position that
+    /// keeps it ordered by job submission order, then stage id, then
+    /// partition id.
+    pub(crate) fn push_schedulable_task(&self, task: TaskDefinition) {
+        let key = self.task_order_key(&task);
+        let mut pending_tasks = self.pending_tasks.write().unwrap();
+        let position = pending_tasks
+            .iter()
+            .position(|existing| self.task_order_key(existing) > key)
+            .unwrap_or(pending_tasks.len());
+        pending_tasks.insert(position, task);
+    }
+
+    /// Pops the next schedulable task in submission order, if any, and
+    /// records it as in-flight so `reschedule_tasks` can find it again.
+    pub(crate) fn pop_schedulable_task(&self) -> Option<TaskDefinition> {
+        let task = self.pending_tasks.write().unwrap().pop_front()?;
+        if let Some(task_id) = task.task_id.as_ref() {
+            self.in_flight
+                .write()
+                .unwrap()
+                .insert(task_key(task_id), task.clone());
+        }
+        Some(task)
+    }
+
+    /// Returns previously-popped tasks to the front of the schedulable
+    /// queue, looked up by the `PartitionId`s a failed launch kept around
+    /// rather than the full `TaskDefinition`s.
+    pub fn reschedule_tasks(&self, task_ids: Vec<PartitionId>) {
+        let mut in_flight = self.in_flight.write().unwrap();
+        let mut pending_tasks = self.pending_tasks.write().unwrap();
+        for task_id in task_ids.iter().rev() {
+            if let Some(task) = in_flight.remove(&task_key(task_id)) {
+                pending_tasks.push_front(task);
+            }
+        }
+    }
+
+    fn task_order_key(&self, task: &TaskDefinition) -> (u64, u32, u32) {
+        match task.task_id.as_ref() {
+            Some(task_id) => (
+                self.job_sequence_number(&task_id.job_id),
+                task_id.stage_id,
+                task_id.partition_id,
+            ),
+            None => (u64::MAX, u32::MAX, u32::MAX),
+        }
+    }
+
+    fn job_sequence_number(&self, job_id: &str) -> u64 {
+        if let Some(seq) = self.job_sequence.read().unwrap().get(job_id) {
+            return *seq;
+        }
+        *self
+            .job_sequence
+            .write()
+            .unwrap()
+            .entry(job_id.to_string())
+            .or_insert_with(|| self.next_job_sequence.fetch_add(1, Ordering::SeqCst))
+    }
+}
+
+fn task_key(task_id: &PartitionId) -> String {
+    format!(
+        "{}/{}/{}",
+        task_id.job_id, task_id.stage_id, task_id.partition_id
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ballista_core::serde::protobuf::{LogicalPlanNode, PhysicalPlanNode};
+
+    fn task(job_id: &str, stage_id: u32, partition_id: u32) -> TaskDefinition {
+        TaskDefinition {
+            task_id: Some(PartitionId {
+                job_id: job_id.to_string(),
+                stage_id,
+                partition_id,
+            }),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn schedulable_tasks_pop_in_submission_order() {
+        let state = SchedulerState::<LogicalPlanNode, PhysicalPlanNode>::default();
+
+        // Job "a" is submitted first, then job "b"; within a job, stage 1
+        // comes before stage 2, and partition 0 before partition 1. Pushing
+        // out of that order (and interleaved across jobs) shouldn't matter:
+        // the queue is kept sorted by submission order regardless of push
+        // order.
+        state.push_schedulable_task(task("a", 2, 1));
+        state.push_schedulable_task(task("b", 0, 0));
+        state.push_schedulable_task(task("a", 1, 0));
+        state.push_schedulable_task(task("a", 1, 1));
+
+        let popped: Vec<(String, u32, u32)> = std::iter::from_fn(|| state.pop_schedulable_task())
+            .map(|t| {
+                let id = t.task_id.unwrap();
+                (id.job_id, id.stage_id, id.partition_id)
+            })
+            .collect();
+
+        assert_eq!(
+            popped,
+            vec![
+                ("a".to_string(), 1, 0),
+                ("a".to_string(), 1, 1),
+                ("a".to_string(), 2, 1),
+                ("b".to_string(), 0, 0),
+            ]
+        );
+    }
+
+    #[test]
+    fn reschedule_tasks_round_trips_through_in_flight() {
+        let state = SchedulerState::<LogicalPlanNode, PhysicalPlanNode>::default();
+        state.push_schedulable_task(task("a", 0, 0));
+
+        let popped = state.pop_schedulable_task().unwrap();
+        let popped_id = popped.task_id.clone().unwrap();
+        assert!(state.pop_schedulable_task().is_none());
+
+        // Simulate a failed launch: the task comes back, found purely from
+        // the `PartitionId` a caller kept around rather than the full
+        // `TaskDefinition`.
+        state.reschedule_tasks(vec![popped_id.clone()]);
+        let requeued = state.pop_schedulable_task().unwrap();
+        assert_eq!(requeued.task_id.unwrap(), popped_id);
+
+        // A `PartitionId` with no matching in-flight entry (already
+        // requeued, or never popped) is silently ignored rather than
+        // fabricating a task.
+        state.reschedule_tasks(vec![PartitionId {
+            job_id: "unknown".to_string(),
+            stage_id: 0,
+            partition_id: 0,
+        }]);
+        assert!(state.pop_schedulable_task().is_none());
+    }
+}
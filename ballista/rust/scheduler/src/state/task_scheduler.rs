@@ -0,0 +1,153 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+use async_trait::async_trait;
+
+use ballista_core::error::Result;
+use ballista_core::serde::protobuf::TaskDefinition;
+use ballista_core::serde::scheduler::ExecutorData;
+use ballista_core::serde::AsExecutionPlan;
+use datafusion_proto::logical_plan::AsLogicalPlan;
+
+use crate::state::SchedulerState;
+
+/// Selects and hands out the next batch of schedulable tasks.
+#[async_trait]
+pub(crate) trait TaskScheduler<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> {
+    /// Walks `executors` and hands each one a round-robin slice of
+    /// schedulable work, up to `n` tasks in total, decrementing
+    /// `available_task_slots` as slots are claimed.
+    async fn fetch_schedulable_tasks(
+        &self,
+        executors: &mut [ExecutorData],
+        n: u32,
+    ) -> Result<(Vec<Vec<TaskDefinition>>, usize)>;
+
+    /// Returns up to `n` currently schedulable tasks across all jobs,
+    /// ordered by job submission time, then stage id, then partition id,
+    /// without assigning them to any executor. Never pops more tasks than
+    /// `executors` have free slots for in total, so nothing is popped only
+    /// to be dropped by the caller's slot-reservation pass with no way
+    /// back onto the queue.
+    async fn get_schedulable_tasks_queue(
+        &self,
+        executors: &[ExecutorData],
+        n: u32,
+    ) -> Result<Vec<TaskDefinition>>;
+}
+
+#[async_trait]
+impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> TaskScheduler<T, U>
+    for SchedulerState<T, U>
+{
+    async fn fetch_schedulable_tasks(
+        &self,
+        executors: &mut [ExecutorData],
+        n: u32,
+    ) -> Result<(Vec<Vec<TaskDefinition>>, usize)> {
+        let mut tasks_assigment: Vec<Vec<TaskDefinition>> = vec![Vec::new(); executors.len()];
+        let mut num_tasks = 0usize;
+
+        'assign: loop {
+            let mut assigned_any = false;
+            for (idx, executor_data) in executors.iter_mut().enumerate() {
+                if num_tasks >= n as usize {
+                    break 'assign;
+                }
+                if executor_data.available_task_slots == 0 {
+                    continue;
+                }
+                let task = match self.pop_schedulable_task() {
+                    Some(task) => task,
+                    None => break 'assign,
+                };
+                executor_data.available_task_slots -= 1;
+                num_tasks += 1;
+                tasks_assigment[idx].push(task);
+                assigned_any = true;
+            }
+            if !assigned_any {
+                break;
+            }
+        }
+
+        Ok((tasks_assigment, num_tasks))
+    }
+
+    async fn get_schedulable_tasks_queue(
+        &self,
+        executors: &[ExecutorData],
+        n: u32,
+    ) -> Result<Vec<TaskDefinition>> {
+        let capacity: u32 = executors.iter().map(|e| e.available_task_slots).sum();
+        let limit = n.min(capacity);
+
+        let mut tasks = Vec::with_capacity(limit as usize);
+        for _ in 0..limit {
+            match self.pop_schedulable_task() {
+                Some(task) => tasks.push(task),
+                None => break,
+            }
+        }
+        Ok(tasks)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ballista_core::serde::protobuf::{LogicalPlanNode, PartitionId, PhysicalPlanNode};
+    use crate::state::SchedulerState;
+
+    fn executor(executor_id: &str, slots: u32) -> ExecutorData {
+        ExecutorData {
+            executor_id: executor_id.to_string(),
+            total_task_slots: slots,
+            available_task_slots: slots,
+        }
+    }
+
+    fn task(partition_id: u32) -> TaskDefinition {
+        TaskDefinition {
+            task_id: Some(PartitionId {
+                job_id: "job".to_string(),
+                stage_id: 0,
+                partition_id,
+            }),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn get_schedulable_tasks_queue_never_pops_more_than_free_slots() {
+        let state = SchedulerState::<LogicalPlanNode, PhysicalPlanNode>::default();
+        for i in 0..5 {
+            state.push_schedulable_task(task(i));
+        }
+        let executors = vec![executor("a", 1), executor("b", 1)];
+
+        // Ask for more than the 2 total free slots can take.
+        let popped = state.get_schedulable_tasks_queue(&executors, 5).await.unwrap();
+
+        assert_eq!(popped.len(), 2);
+        // The rest stay on the queue instead of being silently dropped.
+        assert_eq!(
+            state.get_schedulable_tasks_queue(&executors, 5).await.unwrap().len(),
+            2
+        );
+    }
+}